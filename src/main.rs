@@ -1,13 +1,77 @@
 use anyhow::Context;
 use dioxus::prelude::*;
 use regex::Regex;
+use serde::Deserialize;
 use serde_json::{json, Value};
 use std::cmp::Ordering;
 use std::collections::HashSet;
 use std::env::var;
 use std::fs;
+use std::time::Duration;
 use toml::Table;
 
+/// Modrinth asks integrators to send a unique User-Agent identifying the
+/// application and a way to contact its maintainer, and will block requests
+/// that look like generic library defaults.
+const USER_AGENT_CONTACT: &str = "https://github.com/ToyVo/minecraft_modpack";
+const MAX_RETRIES: u32 = 5;
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Deserialize)]
+struct ApiErrorBody {
+    error: String,
+    description: String,
+}
+
+fn build_http_client() -> Result<reqwest::Client, anyhow::Error> {
+    let user_agent = format!(
+        "ToyVo/minecraft_modpack/{} ({USER_AGENT_CONTACT})",
+        env!("CARGO_PKG_VERSION")
+    );
+    reqwest::Client::builder()
+        .user_agent(user_agent)
+        .build()
+        .context("failed to build http client")
+}
+
+/// Sends the request built by `build_request`, retrying on 429/5xx responses
+/// with the provider's `Retry-After` header or an exponential backoff
+/// (capped at `MAX_RETRIES` retries). On terminal failure the provider's
+/// JSON error body is parsed into a readable message instead of panicking.
+async fn send_with_retry(
+    build_request: impl Fn() -> reqwest::RequestBuilder,
+) -> Result<reqwest::Response, anyhow::Error> {
+    let mut backoff = INITIAL_BACKOFF;
+    for attempt in 0..=MAX_RETRIES {
+        let response = build_request().send().await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        if attempt == MAX_RETRIES || !(status.as_u16() == 429 || status.is_server_error()) {
+            let body = response.json::<ApiErrorBody>().await;
+            return match body {
+                Ok(body) => Err(anyhow::anyhow!(
+                    "{status} {}: {}",
+                    body.error,
+                    body.description
+                )),
+                Err(_) => Err(anyhow::anyhow!("request failed with status {status}")),
+            };
+        }
+        let wait = response
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|header| header.to_str().ok())
+            .and_then(|header| header.parse::<u64>().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(backoff);
+        tokio::time::sleep(wait).await;
+        backoff *= 2;
+    }
+    unreachable!("loop always returns on its last iteration")
+}
+
 #[derive(Clone, Debug)]
 struct ModpackInfo {
     pub name: String,
@@ -31,14 +95,17 @@ fn sort_game_versions(game_versions: &mut [String]) {
     });
 }
 
-async fn get_modrinth_mods(mod_ids: Vec<String>) -> Result<Vec<ModpackInfo>, anyhow::Error> {
+async fn get_modrinth_mods(
+    client: &reqwest::Client,
+    mod_ids: Vec<String>,
+) -> Result<Vec<ModpackInfo>, anyhow::Error> {
     let mut mods = Vec::new();
     if !mod_ids.is_empty() {
         let url = format!(
             "https://api.modrinth.com/v2/projects?ids=[\"{}\"]",
             mod_ids.join("\",\"")
         );
-        let response = reqwest::get(url).await?.error_for_status()?;
+        let response = send_with_retry(|| client.get(&url)).await?;
         let data = response.json::<Vec<Value>>().await?;
         for item in data {
             let name = item.get("title").unwrap().as_str().unwrap().to_string();
@@ -87,20 +154,22 @@ async fn get_modrinth_mods(mod_ids: Vec<String>) -> Result<Vec<ModpackInfo>, any
     Ok(mods)
 }
 
-async fn get_curseforge_mods(mod_ids: Vec<i64>) -> Result<Vec<ModpackInfo>, anyhow::Error> {
+async fn get_curseforge_mods(
+    client: &reqwest::Client,
+    mod_ids: Vec<i64>,
+) -> Result<Vec<ModpackInfo>, anyhow::Error> {
     let mut mods = Vec::new();
     let forge_api_key = var("FORGE_API_KEY")?;
     if !mod_ids.is_empty() {
-        let client = reqwest::Client::new();
-        let response = client
-            .post("https://api.curseforge.com/v1/mods")
-            .header("x-api-key", forge_api_key.as_str())
-            .json(&json!({
-                "modIds": mod_ids,
-            }))
-            .send()
-            .await?
-            .error_for_status()?;
+        let response = send_with_retry(|| {
+            client
+                .post("https://api.curseforge.com/v1/mods")
+                .header("x-api-key", forge_api_key.as_str())
+                .json(&json!({
+                    "modIds": mod_ids,
+                }))
+        })
+        .await?;
         let response = response.json::<Value>().await?;
         let data = response
             .get("data")
@@ -255,9 +324,10 @@ async fn read_modpack() -> Result<Vec<ModpackInfo>, anyhow::Error> {
         }
     }
 
-    let mut modrinth_mods = get_modrinth_mods(mr_mods).await?;
+    let client = build_http_client()?;
+    let mut modrinth_mods = get_modrinth_mods(&client, mr_mods).await?;
     mods.append(&mut modrinth_mods);
-    let mut curseforge_mods = get_curseforge_mods(cf_mods).await?;
+    let mut curseforge_mods = get_curseforge_mods(&client, cf_mods).await?;
     mods.append(&mut curseforge_mods);
 
     mods.sort_by(|a, b| a.name.cmp(&b.name).then(a.side.cmp(&b.side)));